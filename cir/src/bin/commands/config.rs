@@ -1,4 +1,7 @@
 use cir::{
+    bpf_object,
+    cache::Cache,
+    error::Error,
     keymap::{Keymap, LinuxProtocol},
     lirc::Lirc,
     lircd_conf,
@@ -9,21 +12,16 @@ use evdev::KeyCode;
 use irp::{Irp, Options};
 use log::debug;
 use std::{
+    fs,
     path::{Path, PathBuf},
     str::FromStr,
 };
 
-pub fn config(config: &crate::Config) {
-    let mut rcdev = find_devices(&config.device, Purpose::Receive);
+pub fn config(config: &crate::Config) -> Result<(), Error> {
+    let mut rcdev = find_devices(&config.device, Purpose::Receive)?;
 
     if config.delay.is_some() || config.period.is_some() {
-        let inputdev = match rcdev.open_input() {
-            Ok(dev) => dev,
-            Err(e) => {
-                eprintln!("error: input: {e}");
-                std::process::exit(1);
-            }
-        };
+        let inputdev = rcdev.open_input().map_err(Error::Io)?;
 
         let mut repeat = inputdev
             .get_auto_repeat()
@@ -37,73 +35,37 @@ pub fn config(config: &crate::Config) {
             repeat.period = period;
         }
 
-        if let Err(e) = inputdev.update_auto_repeat(&repeat) {
-            eprintln!("error: failed to update autorepeat: {e}");
-            std::process::exit(1);
-        }
+        inputdev.update_auto_repeat(&repeat).map_err(Error::Io)?;
     }
 
     if config.clear {
-        if let Err(e) = rcdev.clear_scancodes() {
-            eprintln!("error: input: {e}");
-            std::process::exit(1);
-        }
+        rcdev.clear_scancodes().map_err(Error::Io)?;
 
         if let Some(lircdev) = &rcdev.lircdev {
-            let lirc = match Lirc::open(PathBuf::from(lircdev)) {
-                Ok(fd) => fd,
-                Err(e) => {
-                    eprintln!("error: {lircdev}: {e}");
-                    std::process::exit(1);
-                }
-            };
+            let lirc = Lirc::open(PathBuf::from(lircdev)).map_err(Error::Io)?;
 
-            if let Err(e) = lirc.clear_bpf() {
-                eprintln!("error: {lircdev}: {e}");
-                std::process::exit(1);
-            }
+            lirc.clear_bpf().map_err(Error::Io)?;
         }
     }
 
     if let Some(timeout) = config.timeout {
-        if let Some(lircdev) = &rcdev.lircdev {
-            let mut lirc = match Lirc::open(PathBuf::from(lircdev)) {
-                Ok(fd) => fd,
-                Err(e) => {
-                    eprintln!("error: {lircdev}: {e}");
-                    std::process::exit(1);
-                }
-            };
+        let Some(lircdev) = &rcdev.lircdev else {
+            return Err(Error::NoLircDevice(rcdev.name.clone()));
+        };
 
-            if let Err(e) = lirc.set_timeout(timeout) {
-                eprintln!("error: {lircdev}: {e}");
-                std::process::exit(1);
-            }
-        } else {
-            eprintln!("error: {}: no lirc device", rcdev.name);
-            std::process::exit(1);
-        }
+        let mut lirc = Lirc::open(PathBuf::from(lircdev)).map_err(Error::Io)?;
+
+        lirc.set_timeout(timeout).map_err(Error::Io)?;
     }
 
     if !config.scankey.is_empty() {
         for (scancode, keycode) in &config.scankey {
-            let key = match KeyCode::from_str(keycode) {
-                Ok(key) => key,
-                Err(_) => {
-                    eprintln!("error: ‘{keycode}’ is not a valid keycode");
-                    continue;
-                }
+            let Ok(key) = KeyCode::from_str(keycode) else {
+                eprintln!("error: ‘{keycode}’ is not a valid keycode");
+                continue;
             };
 
-            match rcdev.update_scancode(key, *scancode) {
-                Ok(_) => (),
-                Err(e) => {
-                    eprintln!(
-                            "error: failed to update key mapping from scancode {scancode:x?} to {key:?}: {e}"
-                        );
-                    std::process::exit(1);
-                }
-            }
+            rcdev.update_scancode(key, *scancode).map_err(Error::Io)?;
         }
     }
 
@@ -124,60 +86,62 @@ pub fn config(config: &crate::Config) {
                     res.push(pos);
                 }
             } else {
-                eprintln!("error: {}: does not support protocol {name}", rcdev.name);
-                std::process::exit(1);
+                return Err(Error::ProtocolNotSupported {
+                    device: rcdev.name.clone(),
+                    protocol: name.clone(),
+                });
             }
         }
 
-        if let Err(e) = rcdev.set_enabled_protocols(&res) {
-            eprintln!("error: {}: {e}", rcdev.name);
-            std::process::exit(1);
-        }
+        rcdev.set_enabled_protocols(&res).map_err(Error::Io)?;
     }
 
-    if let Some(irp_notation) = &config.irp {
-        let irp = match Irp::parse(irp_notation) {
-            Ok(irp) => irp,
-            Err(e) => {
-                eprintln!("error: {irp_notation}: {e}");
-                std::process::exit(1);
-            }
+    if let Some(object_path) = &config.bpf_object {
+        let Some(lircdev) = &rcdev.lircdev else {
+            return Err(Error::NoLircDevice(rcdev.name.clone()));
         };
 
-        let mut max_gap = 100000;
+        let chdev = Lirc::open(PathBuf::from(lircdev)).map_err(Error::Io)?;
 
-        let chdev = if let Some(lircdev) = &rcdev.lircdev {
-            let lirc = match Lirc::open(PathBuf::from(lircdev)) {
-                Ok(fd) => fd,
-                Err(e) => {
-                    eprintln!("error: {lircdev}: {e}");
-                    std::process::exit(1);
-                }
-            };
+        if !chdev.can_receive_raw() {
+            return Err(Error::NotARawReceiver(lircdev.clone()));
+        }
 
-            if !lirc.can_receive_raw() {
-                eprintln!("error: {}: not a raw receiver, irp not supported", lircdev);
-                std::process::exit(1);
-            }
+        match chdev.query_bpf() {
+            Ok(Some(_)) => (),
+            Ok(None) => return Err(Error::BpfNotSupported(lircdev.clone())),
+            Err(e) => return Err(Error::Io(e)),
+        }
 
-            match lirc.query_bpf() {
-                Ok(Some(_)) => (),
-                Ok(None) => {
-                    eprintln!("error: {}: no kernel BPF support, rebuild kernel with CONFIG_BPF_LIRC_MODE2", lircdev);
-                    std::process::exit(1);
-                }
-                Err(e) => {
-                    eprintln!("error: {}: {e}", lircdev);
-                    std::process::exit(1);
-                }
-            }
+        let bpf = bpf_object::read(object_path)?;
 
-            lirc
-        } else {
-            eprintln!("error: {}: no lirc device, irp not supported", rcdev.name);
-            std::process::exit(1);
+        chdev
+            .attach_bpf(&bpf)
+            .map_err(|e| Error::AttachBpf(e.to_string()))?;
+    } else if let Some(irp_notation) = &config.irp {
+        let irp = Irp::parse(irp_notation).map_err(|e| Error::Compile {
+            path: PathBuf::from(irp_notation),
+            message: e.to_string(),
+        })?;
+
+        let mut max_gap = 100000;
+
+        let Some(lircdev) = &rcdev.lircdev else {
+            return Err(Error::NoLircDevice(rcdev.name.clone()));
         };
 
+        let chdev = Lirc::open(PathBuf::from(lircdev)).map_err(Error::Io)?;
+
+        if !chdev.can_receive_raw() {
+            return Err(Error::NotARawReceiver(lircdev.clone()));
+        }
+
+        match chdev.query_bpf() {
+            Ok(Some(_)) => (),
+            Ok(None) => return Err(Error::BpfNotSupported(lircdev.clone())),
+            Err(e) => return Err(Error::Io(e)),
+        }
+
         if let Some(timeout) = config.timeout {
             max_gap = timeout;
         } else if let Ok(timeout) = chdev.get_timeout() {
@@ -207,40 +171,29 @@ pub fn config(config: &crate::Config) {
         options.assembly = config.bpf_options.save_assembly;
         options.object = config.bpf_options.save_object;
 
-        let dfa = match irp.compile(&options) {
-            Ok(dfa) => dfa,
-            Err(e) => {
-                println!("error: irp: {e}");
-                std::process::exit(1);
-            }
-        };
+        let dfa = irp.compile(&options).map_err(|e| Error::Compile {
+            path: PathBuf::from(irp_notation),
+            message: e.to_string(),
+        })?;
 
-        let bpf = match dfa.compile_bpf(&options) {
-            Ok((bpf, _)) => bpf,
-            Err(e) => {
-                eprintln!("error: irp: {e}");
-                std::process::exit(1);
-            }
-        };
+        let (bpf, _) = dfa.compile_bpf(&options).map_err(|e| Error::Compile {
+            path: PathBuf::from(irp_notation),
+            message: e.to_string(),
+        })?;
 
-        if let Err(e) = chdev.attach_bpf(&bpf) {
-            eprintln!("error: attach bpf: {e}",);
-            std::process::exit(1);
-        }
+        chdev
+            .attach_bpf(&bpf)
+            .map_err(|e| Error::AttachBpf(e.to_string()))?;
     }
+
+    Ok(())
 }
 
-pub fn load(load: &crate::Load) {
-    let mut rcdev = find_devices(&load.device, Purpose::Receive);
+pub fn load(load: &crate::Load) -> Result<(), Error> {
+    let mut rcdev = find_devices(&load.device, Purpose::Receive)?;
 
     if load.delay.is_some() || load.period.is_some() {
-        let inputdev = match rcdev.open_input() {
-            Ok(dev) => dev,
-            Err(e) => {
-                eprintln!("error: input: {e}");
-                std::process::exit(1);
-            }
-        };
+        let inputdev = rcdev.open_input().map_err(Error::Io)?;
 
         let mut repeat = inputdev
             .get_auto_repeat()
@@ -254,10 +207,7 @@ pub fn load(load: &crate::Load) {
             repeat.period = period;
         }
 
-        if let Err(e) = inputdev.update_auto_repeat(&repeat) {
-            eprintln!("error: failed to update autorepeat: {e}");
-            std::process::exit(1);
-        }
+        inputdev.update_auto_repeat(&repeat).map_err(Error::Io)?;
     }
 
     load_keymaps(
@@ -265,38 +215,28 @@ pub fn load(load: &crate::Load) {
         &mut rcdev,
         Some(&load.options),
         Some(&load.bpf_options),
+        load.bpf_object.as_deref(),
         &load.keymaps,
-    );
+    )
 }
 
-fn load_keymaps(
+pub(crate) fn load_keymaps(
     clear: bool,
     rcdev: &mut Rcdev,
     decode_options: Option<&crate::DecodeOptions>,
     bpf_decode_options: Option<&crate::BpfDecodeOptions>,
+    bpf_object: Option<&Path>,
     keymaps: &[PathBuf],
-) {
+) -> Result<(), Error> {
     let mut protocols = Vec::new();
 
     let chdev = if clear || !keymaps.is_empty() {
-        if let Err(e) = rcdev.clear_scancodes() {
-            eprintln!("error: {e}");
-            std::process::exit(1);
-        }
+        rcdev.clear_scancodes().map_err(Error::Io)?;
 
         if let Some(lircdev) = &rcdev.lircdev {
-            let lirc = match Lirc::open(PathBuf::from(lircdev)) {
-                Ok(fd) => fd,
-                Err(e) => {
-                    eprintln!("error: {lircdev}: {e}");
-                    std::process::exit(1);
-                }
-            };
+            let lirc = Lirc::open(PathBuf::from(lircdev)).map_err(Error::Io)?;
 
-            if let Err(e) = lirc.clear_bpf() {
-                eprintln!("error: {lircdev}: {e}");
-                std::process::exit(1);
-            }
+            lirc.clear_bpf().map_err(Error::Io)?;
 
             Some(lirc)
         } else {
@@ -313,64 +253,57 @@ fn load_keymaps(
                 &chdev,
                 decode_options,
                 bpf_decode_options,
+                bpf_object,
                 keymap_filename,
-            );
+            )?;
         } else {
             load_keymap(
                 rcdev,
                 &chdev,
                 decode_options,
                 bpf_decode_options,
+                bpf_object,
                 keymap_filename,
                 &mut protocols,
-            );
+            )?;
         }
     }
 
-    if let Err(e) = rcdev.set_enabled_protocols(&protocols) {
-        eprintln!("{e}");
-        std::process::exit(1);
-    }
+    rcdev.set_enabled_protocols(&protocols).map_err(Error::Io)
 }
 
-pub fn auto(auto: &crate::Auto) {
-    let mut rcdev = find_devices(&auto.device, Purpose::Receive);
+pub fn auto(auto: &crate::Auto) -> Result<(), Error> {
+    let mut rcdev = find_devices(&auto.device, Purpose::Receive)?;
 
     if rcdev.inputdev.is_none() {
-        eprintln!("error: {}: input device is missing", rcdev.name);
-        std::process::exit(1);
+        return Err(Error::DeviceNotFound(rcdev.name.clone()));
     }
 
-    match parse_rc_maps_file(&auto.cfgfile) {
-        Ok(keymaps) => {
-            let keymaps: Vec<_> = keymaps
-                .iter()
-                .filter_map(|map| {
-                    if map.matches(&rcdev) {
-                        Some(PathBuf::from(&map.file))
-                    } else {
-                        None
-                    }
-                })
-                .collect();
+    let keymaps = parse_rc_maps_file(&auto.cfgfile).map_err(|e| Error::Compile {
+        path: auto.cfgfile.clone(),
+        message: e.to_string(),
+    })?;
 
-            if keymaps.is_empty() {
-                eprintln!(
-                    "{}: error: no match for driver ‘{}’ and default keymap ‘{}’",
-                    auto.cfgfile.display(),
-                    rcdev.driver,
-                    rcdev.default_keymap
-                );
-                std::process::exit(2);
+    let keymaps: Vec<_> = keymaps
+        .iter()
+        .filter_map(|map| {
+            if map.matches(&rcdev) {
+                Some(PathBuf::from(&map.file))
             } else {
-                load_keymaps(true, &mut rcdev, None, None, &keymaps);
+                None
             }
-        }
-        Err(e) => {
-            eprintln!("error: {}: {e}", auto.cfgfile.display());
-            std::process::exit(1);
-        }
+        })
+        .collect();
+
+    if keymaps.is_empty() {
+        return Err(Error::NoMatchingKeymap {
+            cfgfile: auto.cfgfile.clone(),
+            driver: rcdev.driver.clone(),
+            default_keymap: rcdev.default_keymap.clone(),
+        });
     }
+
+    load_keymaps(true, &mut rcdev, None, None, None, &keymaps)
 }
 
 fn load_keymap(
@@ -378,60 +311,53 @@ fn load_keymap(
     chdev: &Option<Lirc>,
     decode_options: Option<&crate::DecodeOptions>,
     bpf_decode_options: Option<&crate::BpfDecodeOptions>,
+    bpf_object: Option<&Path>,
     keymap_filename: &Path,
     protocols: &mut Vec<usize>,
-) {
-    let keymaps = match Keymap::parse(keymap_filename) {
-        Ok(map) => map,
-        Err(e) => {
-            eprintln!("error: {}: {e}", keymap_filename.display());
-            std::process::exit(1);
-        }
-    };
+) -> Result<(), Error> {
+    let keymaps = Keymap::parse(keymap_filename).map_err(|e| Error::Compile {
+        path: keymap_filename.to_path_buf(),
+        message: e.to_string(),
+    })?;
+
+    let bpf_object = bpf_object.map(bpf_object::read).transpose()?;
+
+    // The cache key has to be derived from the keymap's actual content, not
+    // its name: a name is not unique across files, and an in-place edit
+    // (changed scancodes, a tweaked inline IRP) must never hit a stale
+    // cached object for the same name.
+    let keymap_source = fs::read(keymap_filename).map_err(Error::Io)?;
 
     for keymap in keymaps {
         for (scancode, keycode) in &keymap.scancodes {
             // TODO: needs some logic to check for KEY_{} etc like load_lircd
-            let key = match KeyCode::from_str(keycode) {
-                Ok(key) => key,
-                Err(_) => {
-                    eprintln!("error: ‘{keycode}’ is not a valid keycode");
-                    continue;
-                }
+            let Ok(key) = KeyCode::from_str(keycode) else {
+                eprintln!("error: ‘{keycode}’ is not a valid keycode");
+                continue;
             };
 
-            match rcdev.update_scancode(key, *scancode) {
-                Ok(_) => (),
-                Err(e) => {
-                    eprintln!(
-                            "error: failed to update key mapping from scancode {scancode:x?} to {key:?}: {e}"
-                        );
-                    std::process::exit(1);
-                }
-            }
+            rcdev.update_scancode(key, *scancode).map_err(Error::Io)?;
         }
 
         let Some(chdev) = chdev else {
-            if let Some(p) = LinuxProtocol::find_decoder(&keymap.protocol) {
-                for p in p {
-                    if let Some(index) = rcdev
-                        .supported_protocols
-                        .iter()
-                        .position(|e| e == p.decoder)
-                    {
-                        if !protocols.contains(&index) {
-                            protocols.push(index);
-                        }
-                    } else {
-                        eprintln!("error: no lirc device found for BPF decoding");
-                        std::process::exit(1);
-                    }
+            let Some(decoders) = LinuxProtocol::find_decoder(&keymap.protocol) else {
+                return Err(Error::NoLircDevice(keymap_filename.display().to_string()));
+            };
+
+            for p in decoders {
+                let Some(index) = rcdev
+                    .supported_protocols
+                    .iter()
+                    .position(|e| e == p.decoder)
+                else {
+                    return Err(Error::NoLircDevice(keymap_filename.display().to_string()));
+                };
+
+                if !protocols.contains(&index) {
+                    protocols.push(index);
                 }
-                continue;
-            } else {
-                eprintln!("error: no lirc device found for BPF decoding");
-                std::process::exit(1);
             }
+            continue;
         };
 
         let mut max_gap = 100000;
@@ -467,41 +393,54 @@ fn load_keymap(
             options.object = decode.save_object;
         }
 
-        let dfas = match keymap.build_dfa(&options) {
-            Ok(dfas) => dfas,
-            Err(e) => {
-                println!("{}: {e}", keymap_filename.display());
-                std::process::exit(1);
-            }
-        };
+        // Any --save-* option makes compilation itself an observable side
+        // effect (it is what writes the nfa/dfa/llvm-ir/assembly/object
+        // artifact), so a cache hit - which skips build_dfa/compile_bpf
+        // entirely - must never be used while one of those options is set:
+        // it would silently produce no artifact at all.
+        let save_artifacts =
+            options.nfa || options.dfa || options.llvm_ir || options.assembly || options.object;
+
+        let bpfs = if let Some(bpf) = &bpf_object {
+            log::debug!(
+                "{}: using precompiled bpf object",
+                keymap_filename.display()
+            );
 
-        for dfa in dfas {
-            let bpf = match dfa.compile_bpf(&options) {
-                Ok((bpf, _)) => bpf,
-                Err(e) => {
-                    eprintln!("error: {}: {e}", keymap_filename.display());
-                    std::process::exit(1);
+            vec![bpf.clone()]
+        } else if save_artifacts {
+            compile_keymap_bpfs(&keymap, &options, keymap_filename)?
+        } else {
+            let cache = Cache::open();
+            let cache_key = Cache::key(
+                &[&keymap_source, b"#", keymap.name.as_bytes()].concat(),
+                &options,
+            );
+
+            match cache.get(&cache_key) {
+                Some(bpfs) => {
+                    log::debug!("{}: bpf cache hit ({cache_key})", keymap_filename.display());
+                    bpfs
                 }
-            };
+                None => {
+                    let bpfs = compile_keymap_bpfs(&keymap, &options, keymap_filename)?;
+
+                    cache.put(&cache_key, &bpfs);
 
+                    bpfs
+                }
+            }
+        };
+
+        for bpf in bpfs {
             if !chdev.can_receive_raw() {
-                eprintln!("error: {}: not a raw receiver, irp not supported", chdev);
-                std::process::exit(1);
+                return Err(Error::NotARawReceiver(chdev.to_string()));
             }
 
             match chdev.query_bpf() {
                 Ok(Some(_)) => (),
-                Ok(None) => {
-                    eprintln!(
-                    "error: {}: no kernel BPF support, rebuild kernel with CONFIG_BPF_LIRC_MODE2",
-                    chdev
-                );
-                    std::process::exit(1);
-                }
-                Err(e) => {
-                    eprintln!("error: {}: {e}", chdev);
-                    std::process::exit(1);
-                }
+                Ok(None) => return Err(Error::BpfNotSupported(chdev.to_string())),
+                Err(e) => return Err(Error::Io(e)),
             }
 
             log::debug!(
@@ -510,12 +449,40 @@ fn load_keymap(
                 chdev
             );
 
-            if let Err(e) = chdev.attach_bpf(&bpf) {
-                eprintln!("error: {}: attach bpf: {e}", keymap_filename.display());
-                std::process::exit(1);
-            }
+            chdev
+                .attach_bpf(&bpf)
+                .map_err(|e| Error::AttachBpf(e.to_string()))?;
         }
     }
+
+    Ok(())
+}
+
+/// Build the DFAs for every protocol in `keymap` and compile each to BPF,
+/// the shared path for both a fresh (cache-miss) compile and a forced
+/// recompile when `--save-*` artifact options are set.
+fn compile_keymap_bpfs(
+    keymap: &Keymap,
+    options: &Options,
+    keymap_filename: &Path,
+) -> Result<Vec<Vec<u8>>, Error> {
+    let dfas = keymap.build_dfa(options).map_err(|e| Error::Compile {
+        path: keymap_filename.to_path_buf(),
+        message: e.to_string(),
+    })?;
+
+    let mut bpfs = Vec::new();
+
+    for dfa in dfas {
+        let (bpf, _) = dfa.compile_bpf(options).map_err(|e| Error::Compile {
+            path: keymap_filename.to_path_buf(),
+            message: e.to_string(),
+        })?;
+
+        bpfs.push(bpf);
+    }
+
+    Ok(bpfs)
 }
 
 fn load_lircd(
@@ -523,19 +490,27 @@ fn load_lircd(
     chdev: &Option<Lirc>,
     decode_options: Option<&crate::DecodeOptions>,
     bpf_decode_options: Option<&crate::BpfDecodeOptions>,
+    bpf_object: Option<&Path>,
     keymap_filename: &Path,
-) {
-    let remotes = match lircd_conf::parse(keymap_filename) {
-        Ok(r) => r,
-        Err(_) => std::process::exit(2),
-    };
+) -> Result<(), Error> {
+    let remotes = lircd_conf::parse(keymap_filename).map_err(|e| Error::Compile {
+        path: keymap_filename.to_path_buf(),
+        message: e.to_string(),
+    })?;
+
+    let bpf_object = bpf_object.map(bpf_object::read).transpose()?;
+
+    // As in load_keymap, key the cache on the remote definition's actual
+    // content rather than its path and name, so an in-place edit to the
+    // `.lircd.conf` (changed timings, a tweaked `irp=`) never hits a stale
+    // cached object for a remote of the same name.
+    let lircd_source = fs::read(keymap_filename).map_err(Error::Io)?;
 
     for remote in remotes {
         log::info!("Configuring remote {}", remote.name);
 
         let Some(chdev) = chdev else {
-            eprintln!("error: no lirc device found");
-            std::process::exit(1);
+            return Err(Error::NoLircDevice(keymap_filename.display().to_string()));
         };
 
         let mut max_gap = 100000;
@@ -571,40 +546,67 @@ fn load_lircd(
             options.object = decode.save_object;
         }
 
-        let dfa = remote.build_dfa(&options);
+        // As in load_keymap, a --save-* option makes compilation itself an
+        // observable side effect, so it must bypass the cache rather than
+        // risk a hit that silently skips writing the artifact.
+        let save_artifacts =
+            options.nfa || options.dfa || options.llvm_ir || options.assembly || options.object;
+
+        let compile = |options: &Options| -> Result<Vec<u8>, Error> {
+            let dfa = remote.build_dfa(options);
+
+            let (bpf, _) = dfa.compile_bpf(options).map_err(|e| Error::Compile {
+                path: keymap_filename.to_path_buf(),
+                message: e.to_string(),
+            })?;
+
+            Ok(bpf)
+        };
+
+        let bpf = if let Some(bpf) = &bpf_object {
+            log::debug!(
+                "{}: using precompiled bpf object",
+                keymap_filename.display()
+            );
+
+            bpf.clone()
+        } else if save_artifacts {
+            compile(&options)?
+        } else {
+            let cache = Cache::open();
+            let cache_key = Cache::key(
+                &[&lircd_source, b"#", remote.name.as_bytes()].concat(),
+                &options,
+            );
+
+            match cache.get(&cache_key) {
+                Some(mut bpfs) if !bpfs.is_empty() => {
+                    log::debug!("{}: bpf cache hit ({cache_key})", keymap_filename.display());
+                    bpfs.remove(0)
+                }
+                _ => {
+                    let bpf = compile(&options)?;
+
+                    cache.put(&cache_key, std::slice::from_ref(&bpf));
 
-        let bpf = match dfa.compile_bpf(&options) {
-            Ok((bpf, _)) => bpf,
-            Err(e) => {
-                eprintln!("error: {}: {e}", keymap_filename.display());
-                std::process::exit(1);
+                    bpf
+                }
             }
         };
 
         if !chdev.can_receive_raw() {
-            eprintln!("error: {}: not a raw receiver, irp not supported", chdev);
-            std::process::exit(1);
+            return Err(Error::NotARawReceiver(chdev.to_string()));
         }
 
         match chdev.query_bpf() {
             Ok(Some(_)) => (),
-            Ok(None) => {
-                eprintln!(
-                    "error: {}: no kernel BPF support, rebuild kernel with CONFIG_BPF_LIRC_MODE2",
-                    chdev
-                );
-                std::process::exit(1);
-            }
-            Err(e) => {
-                eprintln!("error: {}: {e}", chdev);
-                std::process::exit(1);
-            }
+            Ok(None) => return Err(Error::BpfNotSupported(chdev.to_string())),
+            Err(e) => return Err(Error::Io(e)),
         }
 
-        if let Err(e) = chdev.attach_bpf(&bpf) {
-            eprintln!("error: {}: attach bpf: {e}", keymap_filename.display());
-            std::process::exit(1);
-        }
+        chdev
+            .attach_bpf(&bpf)
+            .map_err(|e| Error::AttachBpf(e.to_string()))?;
 
         log::debug!("attaching bpf program for {} to {}", remote.name, chdev);
 
@@ -613,34 +615,26 @@ fn load_lircd(
             if !name.starts_with("KEY_") {
                 name.insert_str(0, "KEY_");
             };
-            let key = match KeyCode::from_str(&name) {
-                Ok(key) => key,
-                Err(_) => {
-                    eprintln!(
-                        "error: {}:{}: ‘{}’ is not a valid keycode for remote ‘{}’",
-                        keymap_filename.display(),
-                        code.line_no,
-                        code.name,
-                        remote.name,
-                    );
-                    continue;
-                }
+            let Ok(key) = KeyCode::from_str(&name) else {
+                eprintln!(
+                    "error: {}:{}: ‘{}’ is not a valid keycode for remote ‘{}’",
+                    keymap_filename.display(),
+                    code.line_no,
+                    code.name,
+                    remote.name,
+                );
+                continue;
             };
 
-            match rcdev.update_scancode(key, code.code[0]) {
-                Ok(_) => (),
-                Err(e) => {
-                    eprintln!(
-                        "error: failed to update key mapping from scancode {:x?} to {key:?}: {e}",
-                        code.code[0]
-                    );
-                    std::process::exit(1);
-                }
-            }
+            rcdev
+                .update_scancode(key, code.code[0])
+                .map_err(Error::Io)?;
         }
 
         // TODO: keycodes for raw codes
     }
+
+    Ok(())
 }
 
 pub enum Purpose {
@@ -649,82 +643,59 @@ pub enum Purpose {
 }
 
 /// Enumerate all rc devices and find the lirc and input devices
-pub fn find_devices(device: &crate::RcDevice, purpose: Purpose) -> Rcdev {
-    let mut list = match enumerate_rc_dev() {
-        Ok(list) if list.is_empty() => {
-            eprintln!("error: no devices found");
-            std::process::exit(1);
-        }
-        Ok(list) => list,
-        Err(err) => {
-            eprintln!("error: no devices found: {err}");
-            std::process::exit(1);
-        }
-    };
+pub fn find_devices(device: &crate::RcDevice, purpose: Purpose) -> Result<Rcdev, Error> {
+    let mut list = enumerate_rc_dev().map_err(Error::Io)?;
+
+    if list.is_empty() {
+        return Err(Error::NoDevicesFound);
+    }
 
     let entry = if let Some(rcdev) = &device.rc_dev {
-        if let Some(entry) = list.iter().position(|rc| &rc.name == rcdev) {
-            entry
-        } else {
-            eprintln!("error: {rcdev} not found");
-            std::process::exit(1);
-        }
+        list.iter()
+            .position(|rc| &rc.name == rcdev)
+            .ok_or_else(|| Error::DeviceNotFound(rcdev.clone()))?
     } else if let Some(lircdev) = &device.lirc_dev {
-        if let Some(entry) = list
-            .iter()
+        list.iter()
             .position(|rc| rc.lircdev == Some(lircdev.to_string()))
-        {
-            entry
-        } else {
-            eprintln!("error: {lircdev} not found");
-            std::process::exit(1);
-        }
-    } else if let Some(entry) = list.iter().position(|rc| {
-        if rc.lircdev.is_none() {
-            false
-        } else {
-            let lircpath = PathBuf::from(rc.lircdev.as_ref().unwrap());
+            .ok_or_else(|| Error::DeviceNotFound(lircdev.clone()))?
+    } else {
+        let mut found = None;
 
-            let lirc = match Lirc::open(&lircpath) {
-                Ok(l) => l,
-                Err(e) => {
-                    eprintln!("error: {}: {}", lircpath.display(), e);
-                    std::process::exit(1);
-                }
+        for (pos, rc) in list.iter().enumerate() {
+            let Some(lircdev) = &rc.lircdev else {
+                continue;
             };
 
-            match purpose {
+            let lircpath = PathBuf::from(lircdev);
+            let lirc = Lirc::open(&lircpath).map_err(Error::Io)?;
+
+            let matches = match purpose {
                 Purpose::Receive => lirc.can_receive_raw() || lirc.can_receive_scancodes(),
                 Purpose::Transmit => lirc.can_send(),
+            };
+
+            if matches {
+                found = Some(pos);
+                break;
             }
         }
-    }) {
-        entry
-    } else {
-        eprintln!("error: no lirc device found");
-        std::process::exit(1);
+
+        found.ok_or_else(|| Error::NoLircDevice(String::new()))?
     };
 
-    list.remove(entry)
+    Ok(list.remove(entry))
 }
 
-pub fn open_lirc(device: &crate::RcDevice, purpose: Purpose) -> Lirc {
-    let rcdev = find_devices(device, purpose);
+pub fn open_lirc(device: &crate::RcDevice, purpose: Purpose) -> Result<Lirc, Error> {
+    let rcdev = find_devices(device, purpose)?;
+
+    let Some(lircdev) = rcdev.lircdev else {
+        return Err(Error::NoLircDevice(rcdev.name));
+    };
 
-    if let Some(lircdev) = rcdev.lircdev {
-        debug!("opening {}", lircdev);
+    debug!("opening {}", lircdev);
 
-        let lircpath = PathBuf::from(lircdev);
+    let lircpath = PathBuf::from(lircdev);
 
-        match Lirc::open(&lircpath) {
-            Ok(l) => l,
-            Err(s) => {
-                eprintln!("error: {}: {}", lircpath.display(), s);
-                std::process::exit(1);
-            }
-        }
-    } else {
-        eprintln!("error: no lirc device found");
-        std::process::exit(1);
-    }
+    Lirc::open(&lircpath).map_err(Error::Io)
 }
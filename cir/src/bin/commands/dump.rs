@@ -0,0 +1,95 @@
+use super::config::{find_devices, open_lirc, Purpose};
+use cir::{error::Error, keymap::Keymap};
+use serde::Serialize;
+use std::{collections::BTreeMap, fs};
+
+/// Mirrors the `[[protocols]]` array-of-tables shape `Keymap::parse` reads a
+/// keymap file as, so a device with more than one enabled protocol dumps
+/// back into the same multi-entry form `load` can re-parse, rather than one
+/// `Keymap` per file with its `protocol` field holding several names jammed
+/// together.
+#[derive(Serialize)]
+struct Keymaps<'a> {
+    protocols: &'a [Keymap],
+}
+
+/// Read back a device's live kernel configuration and emit it as a keymap
+/// TOML that `load` can re-apply: the inverse of `load`/`config`, useful for
+/// backing up a device's configuration or inspecting exactly what a
+/// `.lircd.conf` or IRP load actually programmed into the kernel.
+pub fn dump(dump: &crate::Dump) -> Result<(), Error> {
+    let mut rcdev = find_devices(&dump.device, Purpose::Receive)?;
+
+    // `Keymap.protocol` is fed straight to `LinuxProtocol::find_decoder`,
+    // which expects a single protocol name, so a device with more than one
+    // enabled protocol needs one keymap entry per protocol here rather than
+    // one space-joined string `load` could never parse back.
+    let protocols: Vec<String> = rcdev
+        .enabled_protocols()
+        .map_err(Error::Io)?
+        .iter()
+        .filter_map(|index| rcdev.supported_protocols.get(*index))
+        .cloned()
+        .collect();
+
+    let scancodes: BTreeMap<u64, String> = rcdev
+        .scancodes()
+        .map_err(Error::Io)?
+        .into_iter()
+        .map(|(scancode, keycode)| (scancode, keycode.to_string()))
+        .collect();
+
+    // The kernel scancode table is per-device, not per-protocol, so it is
+    // attached to the first entry only; repeating it on every entry would
+    // make `load` reprogram the whole table once per enabled protocol.
+    let keymaps: Vec<Keymap> = protocols
+        .into_iter()
+        .enumerate()
+        .map(|(index, protocol)| Keymap {
+            name: rcdev.name.clone(),
+            protocol,
+            scancodes: if index == 0 {
+                scancodes.clone()
+            } else {
+                BTreeMap::new()
+            },
+            ..Default::default()
+        })
+        .collect();
+
+    let mut toml = toml::to_string_pretty(&Keymaps {
+        protocols: &keymaps,
+    })
+    .map_err(|e| Error::Keymap(e.to_string()))?;
+
+    // The timeout and autorepeat delay/period are device settings, not part
+    // of the keymap format: `load`/`config` only ever take them as CLI flags
+    // (`--timeout`, `--delay`, `--period`), never read them back out of a
+    // keymap file. So this dump cannot be a lossless round-trip via `load
+    // <file>` alone - surface the current values as comments, along with the
+    // `config` invocation that restores them, rather than silently drop them
+    // or imply `load` will pick them up on its own.
+    if let Ok(chdev) = open_lirc(&dump.device, Purpose::Receive) {
+        if let Ok(timeout) = chdev.get_timeout() {
+            toml = format!(
+                "# timeout = {timeout} (not restored by `load`; run `cir config --timeout {timeout}` to restore)\n{toml}"
+            );
+        }
+    }
+
+    if let Ok(inputdev) = rcdev.open_input() {
+        if let Ok(repeat) = inputdev.get_auto_repeat() {
+            toml = format!(
+                "# autorepeat delay = {0}, period = {1} (not restored by `load`; run `cir config --delay {0} --period {1}` to restore)\n{toml}",
+                repeat.delay, repeat.period
+            );
+        }
+    }
+
+    match &dump.output {
+        Some(path) => fs::write(path, toml).map_err(Error::Io)?,
+        None => print!("{toml}"),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,159 @@
+use super::config::load_keymaps;
+use cir::rc_maps::parse_rc_maps_file;
+use cir::rcdev::enumerate_rc_dev;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+use udev::{EventType, MonitorBuilder};
+
+/// How long to keep retrying to find a device's lirc sibling node after its
+/// input node has already appeared, since the two enumerate asynchronously.
+const LIRC_SIBLING_TIMEOUT: Duration = Duration::from_secs(2);
+const LIRC_SIBLING_RETRY: Duration = Duration::from_millis(100);
+
+/// Run forever, auto-configuring rc devices as they are hotplugged.
+///
+/// This does the same thing `auto()` does for a single, already-present
+/// device, but driven from udev `add`/`change` events on the `rc` and
+/// `lirc` subsystems instead of a one-shot enumeration, so `cir` stays
+/// correctly configured across replug without a manual re-run.
+pub fn monitor(monitor: &crate::Monitor) {
+    let mut udev_monitor = match MonitorBuilder::new() {
+        Ok(builder) => builder,
+        Err(e) => {
+            eprintln!("error: failed to open udev monitor: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    for subsystem in ["rc", "lirc"] {
+        udev_monitor = match udev_monitor.match_subsystem(subsystem) {
+            Ok(builder) => builder,
+            Err(e) => {
+                eprintln!("error: failed to watch {subsystem} subsystem: {e}");
+                std::process::exit(1);
+            }
+        };
+    }
+
+    let socket = match udev_monitor.listen() {
+        Ok(socket) => socket,
+        Err(e) => {
+            eprintln!("error: failed to listen for udev events: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    log::info!("monitoring rc/lirc devices for hotplug events");
+
+    // names of devices we have already configured, so a coalesced run of
+    // add/change events for the same device is not reapplied every time
+    let mut configured = HashSet::new();
+
+    // do an initial pass in case a device is already plugged in
+    configure_new_devices(&monitor.cfgfile, &mut configured);
+
+    loop {
+        for event in socket.iter() {
+            match event.event_type() {
+                EventType::Add | EventType::Change => {
+                    configure_new_devices(&monitor.cfgfile, &mut configured);
+                }
+                EventType::Remove => {
+                    // rc devices are tracked by their sysfs name (`rc0`),
+                    // which is the sysname of the removed rc device
+                    configured.remove(&event.sysname().to_string_lossy().to_string());
+                }
+                _ => (),
+            }
+        }
+
+        thread::sleep(Duration::from_millis(200));
+    }
+}
+
+fn configure_new_devices(cfgfile: &Path, configured: &mut HashSet<String>) {
+    let devices = match enumerate_rc_dev() {
+        Ok(devices) => devices,
+        Err(e) => {
+            log::debug!("failed to enumerate rc devices: {e}");
+            return;
+        }
+    };
+
+    for mut rcdev in devices {
+        if rcdev.inputdev.is_none() || configured.contains(&rcdev.name) {
+            continue;
+        }
+
+        wait_for_lircdev(&mut rcdev);
+
+        let keymaps = match parse_rc_maps_file(cfgfile) {
+            Ok(keymaps) => keymaps,
+            Err(e) => {
+                log::error!("{}: {e}", cfgfile.display());
+                continue;
+            }
+        };
+
+        let keymaps: Vec<PathBuf> = keymaps
+            .iter()
+            .filter_map(|map| {
+                if map.matches(&rcdev) {
+                    Some(PathBuf::from(&map.file))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        if keymaps.is_empty() {
+            log::debug!(
+                "{}: no match for driver ‘{}’ and default keymap ‘{}’",
+                cfgfile.display(),
+                rcdev.driver,
+                rcdev.default_keymap
+            );
+            continue;
+        }
+
+        log::info!("configuring {} ({})", rcdev.name, rcdev.driver);
+
+        let name = rcdev.name.clone();
+
+        if let Err(e) = load_keymaps(true, &mut rcdev, None, None, None, &keymaps) {
+            log::error!("{name}: {e}");
+            continue;
+        }
+
+        configured.insert(name);
+    }
+}
+
+/// The lirc child device node of a newly-added rc device enumerates
+/// asynchronously after the input node, so retry for a short window rather
+/// than treating a missing lirc node as permanent.
+fn wait_for_lircdev(rcdev: &mut cir::rcdev::Rcdev) {
+    if rcdev.lircdev.is_some() {
+        return;
+    }
+
+    let deadline = Instant::now() + LIRC_SIBLING_TIMEOUT;
+
+    while rcdev.lircdev.is_none() && Instant::now() < deadline {
+        thread::sleep(LIRC_SIBLING_RETRY);
+
+        let Ok(devices) = enumerate_rc_dev() else {
+            return;
+        };
+
+        let Some(refreshed) = devices.into_iter().find(|d| d.name == rcdev.name) else {
+            return;
+        };
+
+        rcdev.lircdev = refreshed.lircdev;
+    }
+}
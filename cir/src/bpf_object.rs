@@ -0,0 +1,116 @@
+use crate::error::Error;
+use std::{fs, path::Path};
+
+/// ELF `e_machine` value for BPF, as assigned by the Linux kernel headers.
+const EM_BPF: u16 = 247;
+
+/// ELF `sh_type` for a section holding program data (as opposed to e.g. a
+/// symbol table or relocations).
+const SHT_PROGBITS: u32 = 1;
+
+/// ELF `sh_flags` bit marking a section as containing executable
+/// instructions.
+const SHF_EXECINSTR: u64 = 0x4;
+
+/// `attach_bpf` loads the object with a raw `bpf()` syscall rather than a
+/// libbpf-style loader that finds the program by a conventional `SEC(...)`
+/// name, so the only section-layout property it actually relies on is that
+/// there is a program to find at all: an executable `PROGBITS` section
+/// holding the compiled instructions, which is what `compile_bpf` emits.
+///
+/// Read a precompiled BPF ELF object from `path` and confirm it is shaped
+/// like a LIRC mode2 decoder before handing it to `Lirc::attach_bpf`:
+/// a 64-bit little-endian ELF object, targeting the BPF machine type, with
+/// at least one executable program section. This lets a prebuilt `.o`
+/// produced by `compile_bpf`/`--save-object` on one machine be loaded
+/// verbatim on a target that lacks the LLVM toolchain, rather than always
+/// recompiling from IRP or a `.lircd.conf` remote.
+pub fn read(path: &Path) -> Result<Vec<u8>, Error> {
+    let data = fs::read(path).map_err(Error::Io)?;
+
+    validate(&data).map_err(|message| Error::InvalidBpfObject {
+        path: path.to_path_buf(),
+        message,
+    })?;
+
+    Ok(data)
+}
+
+fn validate(data: &[u8]) -> Result<(), String> {
+    if data.len() < 64 || &data[0..4] != b"\x7fELF" {
+        return Err("not an ELF object".to_string());
+    }
+
+    if data[4] != 2 {
+        return Err("expected a 64-bit ELF object".to_string());
+    }
+
+    if data[5] != 1 {
+        return Err("expected a little-endian ELF object".to_string());
+    }
+
+    let e_machine = read_u16(data, 18)?;
+    if e_machine != EM_BPF {
+        return Err(format!(
+            "expected an EM_BPF (e_machine={EM_BPF}) object, found e_machine={e_machine}"
+        ));
+    }
+
+    let e_shoff = read_u64(data, 40)? as usize;
+    let e_shentsize = read_u16(data, 58)? as usize;
+    let e_shnum = read_u16(data, 60)? as usize;
+
+    if e_shentsize < 64 || e_shnum == 0 {
+        return Err("object has no section headers".to_string());
+    }
+
+    let shdr = |index: usize| -> Result<&[u8], String> {
+        let start = e_shoff
+            .checked_add(
+                index
+                    .checked_mul(e_shentsize)
+                    .ok_or("section table overflow")?,
+            )
+            .ok_or("section table overflow")?;
+
+        let end = start
+            .checked_add(e_shentsize)
+            .ok_or("section table overflow")?;
+
+        data.get(start..end)
+            .ok_or_else(|| "section header out of bounds".to_string())
+    };
+
+    let has_executable_section = (0..e_shnum).any(|index| {
+        shdr(index)
+            .ok()
+            .and_then(|sh| Some((read_u32(sh, 4).ok()?, read_u64(sh, 8).ok()?)))
+            .is_some_and(|(sh_type, sh_flags)| {
+                sh_type == SHT_PROGBITS && sh_flags & SHF_EXECINSTR != 0
+            })
+    });
+
+    if !has_executable_section {
+        return Err("no executable program section found".to_string());
+    }
+
+    Ok(())
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, String> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| "truncated ELF header".to_string())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, String> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| "truncated ELF header".to_string())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, String> {
+    data.get(offset..offset + 8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| "truncated ELF header".to_string())
+}
@@ -0,0 +1,117 @@
+use irp::Options;
+use sha2::{Digest, Sha256};
+use std::{fs, path::PathBuf};
+
+/// Content-addressed cache of compiled BPF objects, so reloading an
+/// unchanged keymap after a replug or reboot does not repay the
+/// NFA → DFA → LLVM → BPF pipeline every time.
+///
+/// A keymap can compile down to more than one BPF object (one per protocol
+/// variant), so each cache entry stores the whole ordered set produced for a
+/// given key rather than a single object; a lircd.conf remote's entry holds
+/// just one.
+pub struct Cache {
+    dir: Option<PathBuf>,
+}
+
+impl Cache {
+    /// Open the on-disk cache under the XDG cache directory (typically
+    /// `~/.cache/cir/bpf`). If the cache directory cannot be determined or
+    /// created, caching is silently disabled and every lookup misses.
+    pub fn open() -> Cache {
+        let dir = dirs::cache_dir().map(|dir| dir.join("cir").join("bpf"));
+
+        let dir = match &dir {
+            Some(dir) => match fs::create_dir_all(dir) {
+                Ok(()) => Some(dir.clone()),
+                Err(e) => {
+                    log::debug!("could not create bpf cache dir {}: {e}", dir.display());
+                    None
+                }
+            },
+            None => None,
+        };
+
+        Cache { dir }
+    }
+
+    /// Derive a stable cache key from the compiled source — the IRP
+    /// notation, or the raw bytes of the keymap/`lircd.conf` definition
+    /// that was actually parsed, not just its name or path — together with
+    /// the `Options` fields that affect codegen. Any change to the source
+    /// content or these fields changes the key, so a stale entry is simply
+    /// never looked up again rather than needing explicit invalidation, and
+    /// two different definitions that happen to share a name never collide.
+    pub fn key(source: &[u8], options: &Options) -> String {
+        let mut hasher = Sha256::new();
+
+        hasher.update(source);
+        hasher.update(options.aeps.to_le_bytes());
+        hasher.update(options.eps.to_le_bytes());
+        hasher.update(options.max_gap.to_le_bytes());
+        hasher.update(options.repeat_mask.to_le_bytes());
+
+        format!("{:x}", hasher.finalize())
+    }
+
+    /// Look up the compiled BPF objects for `key`, in the order they were
+    /// stored, on a cache hit.
+    pub fn get(&self, key: &str) -> Option<Vec<Vec<u8>>> {
+        let dir = self.dir.as_ref()?;
+
+        let data = fs::read(dir.join(key)).ok()?;
+
+        decode_objects(&data)
+    }
+
+    /// Store the compiled BPF objects produced for `key`. Failure to write
+    /// the cache is not fatal: the objects were already compiled, so the
+    /// load just proceeds without having sped up the next one.
+    pub fn put(&self, key: &str, objects: &[Vec<u8>]) {
+        let Some(dir) = &self.dir else {
+            return;
+        };
+
+        if let Err(e) = fs::write(dir.join(key), encode_objects(objects)) {
+            log::debug!("could not write bpf cache entry {key}: {e}");
+        }
+    }
+}
+
+/// `count: u32` followed by `count` `(len: u64, bytes)` entries.
+fn encode_objects(objects: &[Vec<u8>]) -> Vec<u8> {
+    let mut data = Vec::new();
+
+    data.extend_from_slice(&(objects.len() as u32).to_le_bytes());
+
+    for object in objects {
+        data.extend_from_slice(&(object.len() as u64).to_le_bytes());
+        data.extend_from_slice(object);
+    }
+
+    data
+}
+
+fn decode_objects(data: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let read_u32 = |data: &[u8]| -> Option<(u32, &[u8])> {
+        let (head, tail) = data.split_at_checked(4)?;
+        Some((u32::from_le_bytes(head.try_into().ok()?), tail))
+    };
+    let read_u64 = |data: &[u8]| -> Option<(u64, &[u8])> {
+        let (head, tail) = data.split_at_checked(8)?;
+        Some((u64::from_le_bytes(head.try_into().ok()?), tail))
+    };
+
+    let (count, mut rest) = read_u32(data)?;
+    let mut objects = Vec::with_capacity(count as usize);
+
+    for _ in 0..count {
+        let (len, tail) = read_u64(rest)?;
+        let (object, tail) = tail.split_at_checked(len as usize)?;
+
+        objects.push(object.to_vec());
+        rest = tail;
+    }
+
+    Some(objects)
+}
@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Errors produced by the `cir` device-configuration commands.
+///
+/// `config`, `load`, `auto`, `dump` and the functions they call return these
+/// instead of printing and calling `process::exit`, so the crate can be
+/// embedded and tested without forking: callers get a `Result` back. An
+/// invalid keycode in a keymap, a `.lircd.conf` remote or on the command
+/// line is not returned as an error: it is reported on stderr and skipped,
+/// and the rest of the keymap is still loaded. `monitor` still prints and
+/// exits when it cannot set up its udev monitor.
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("no rc devices found")]
+    NoDevicesFound,
+
+    #[error("{0}: device not found")]
+    DeviceNotFound(String),
+
+    #[error("{device}: does not support protocol {protocol}")]
+    ProtocolNotSupported { device: String, protocol: String },
+
+    #[error("{0}: not a raw receiver, irp not supported")]
+    NotARawReceiver(String),
+
+    #[error("{0}: no kernel BPF support, rebuild kernel with CONFIG_BPF_LIRC_MODE2")]
+    BpfNotSupported(String),
+
+    #[error("‘{0}’ is not a valid keycode")]
+    InvalidKeycode(String),
+
+    #[error("{path}:{line_no}: ‘{keycode}’ is not a valid keycode for remote ‘{remote}’")]
+    InvalidRemoteKeycode {
+        path: PathBuf,
+        line_no: u32,
+        keycode: String,
+        remote: String,
+    },
+
+    #[error("{0}: no lirc device found")]
+    NoLircDevice(String),
+
+    #[error("{cfgfile}: no match for driver ‘{driver}’ and default keymap ‘{default_keymap}’")]
+    NoMatchingKeymap {
+        cfgfile: PathBuf,
+        driver: String,
+        default_keymap: String,
+    },
+
+    #[error("{path}: {message}")]
+    Compile { path: PathBuf, message: String },
+
+    #[error("{path}: not a valid LIRC BPF object: {message}")]
+    InvalidBpfObject { path: PathBuf, message: String },
+
+    #[error("attach bpf: {0}")]
+    AttachBpf(String),
+
+    #[error("{0}")]
+    Keymap(String),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
@@ -3,22 +3,238 @@ use super::{
     Vartable,
 };
 use itertools::Itertools;
-use std::{char, fs::File, io::Write, path::PathBuf};
+use std::{
+    char,
+    ffi::OsStr,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Which file format a graph should be rendered to.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum GraphvizFormat {
+    /// Write the GraphViz `.dot` source and nothing else
+    Dot,
+    /// Render to SVG via the `dot` tool
+    Svg,
+    /// Render to PNG via the `dot` tool
+    Png,
+}
+
+impl GraphvizFormat {
+    fn dot_type_flag(self) -> &'static str {
+        match self {
+            GraphvizFormat::Dot => "dot",
+            GraphvizFormat::Svg => "svg",
+            GraphvizFormat::Png => "png",
+        }
+    }
 
-/// Generate a GraphViz dot file and write to the given path
+    pub fn extension(self) -> &'static str {
+        match self {
+            GraphvizFormat::Dot => "dot",
+            GraphvizFormat::Svg => "svg",
+            GraphvizFormat::Png => "png",
+        }
+    }
+}
+
+/// Generate a GraphViz graph and write to the given path. The format is
+/// guessed from `path`'s extension (`.svg`/`.png` render through the `dot`
+/// tool, anything else is written as dot source).
 pub(crate) fn graphviz(verts: &[Vertex], name: &str, states: &[(usize, Vartable)], path: &str) {
-    let path = PathBuf::from(path);
-    let mut file = File::create(path).expect("create file");
+    render(
+        verts,
+        name,
+        states,
+        path,
+        format_from_extension(Path::new(path)),
+    );
+}
+
+/// Generate a GraphViz graph and write it to `path` in the given format. For
+/// `GraphvizFormat::Dot` this just writes the dot source; for SVG/PNG the dot
+/// source is piped through the `dot` tool from the local GraphViz install.
+pub(crate) fn render(
+    verts: &[Vertex],
+    name: &str,
+    states: &[(usize, Vartable)],
+    path: &str,
+    format: GraphvizFormat,
+) {
+    let dot_source = dot_source(verts, name, states);
+
+    if format == GraphvizFormat::Dot {
+        let mut file = File::create(path).expect("create file");
+
+        file.write_all(dot_source.as_bytes()).expect("write file");
+    } else {
+        run_dot(&dot_source, path, format);
+    }
+}
+
+/// Combine a series of per-step `.dot` files (as written by repeated calls
+/// to [`graphviz`], one per matcher input) into a single document: each
+/// step's graph becomes its own labelled cluster, so the whole matcher run
+/// can be viewed or rendered as one artifact instead of hundreds of loose
+/// files. The source `step_paths` are removed once they have been folded
+/// into the combined document.
+pub fn combine_steps(step_paths: &[PathBuf], name: &str, path: &str, format: GraphvizFormat) {
+    let mut dot_source = format!("strict digraph {name} {{\n");
+
+    for (step, step_path) in step_paths.iter().enumerate() {
+        let body = fs::read_to_string(step_path).expect("read step dot file");
+        let body = strip_digraph_wrapper(&body);
+        let body = namespace_node_ids(body, step);
+
+        dot_source.push_str(&format!("\tsubgraph cluster_{step} {{\n"));
+        dot_source.push_str(&format!("\t\tlabel = \"step {step}\";\n"));
+
+        for line in body.lines() {
+            dot_source.push_str("\t\t");
+            dot_source.push_str(line);
+            dot_source.push('\n');
+        }
+
+        dot_source.push_str("\t}\n");
+    }
+
+    dot_source.push_str("}\n");
+
+    if format == GraphvizFormat::Dot {
+        let mut file = File::create(path).expect("create file");
+
+        file.write_all(dot_source.as_bytes()).expect("write file");
+    } else {
+        run_dot(&dot_source, path, format);
+    }
+
+    for step_path in step_paths {
+        let _ = fs::remove_file(step_path);
+    }
+}
+
+/// Namespace a step's node ids with its step index. GraphViz node identity
+/// is global, not scoped to the `subgraph cluster_N` it is declared in, so
+/// without this every step's same-named vertices (e.g. `"irp done (3)"`)
+/// would collapse onto one shared node across the whole combined document,
+/// losing the per-step `[color=red]` current-state highlight this function
+/// exists to preserve.
+fn namespace_node_ids(body: &str, step: usize) -> String {
+    body.lines()
+        .map(|line| namespace_line(line, step))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Rewrite the (at most two) leading quoted node ids on a single dot source
+/// line - `"name"`, or `"src" -> "dest"` - leaving everything after them,
+/// including a trailing `[label="..."]`, untouched.
+fn namespace_line(line: &str, step: usize) -> String {
+    let mut remainder = line;
+    let mut out = take_and_namespace_node(&mut remainder, step);
+
+    if let Some(rest) = remainder.strip_prefix(" -> ") {
+        remainder = rest;
+        out.push_str(" -> ");
+        out.push_str(&take_and_namespace_node(&mut remainder, step));
+    }
+
+    out.push_str(remainder);
+    out
+}
 
-    writeln!(&mut file, "strict digraph {name} {{").unwrap();
+fn take_and_namespace_node(remainder: &mut &str, step: usize) -> String {
+    let Some(rest) = remainder.strip_prefix('"') else {
+        return String::new();
+    };
+
+    let Some(end) = rest.find('"') else {
+        return String::new();
+    };
+
+    let name = &rest[..end];
+    *remainder = &rest[end + 1..];
+
+    format!("\"step{step}: {name}\"")
+}
+
+/// Strip the `strict digraph <name> {` / `}` wrapper around a per-step dot
+/// file's body. The per-step files are written independently (e.g. by
+/// `Matcher::dotgraphviz`), so their digraph name is not guaranteed to match
+/// the `name` `combine_steps` was called with; parsing the header that is
+/// actually there, rather than assuming a particular name, is what keeps a
+/// mismatch from embedding a whole standalone digraph inside a
+/// `subgraph cluster_N` and producing dot source `dot` rejects.
+fn strip_digraph_wrapper(body: &str) -> &str {
+    let inner = body
+        .strip_prefix("strict digraph ")
+        .and_then(|rest| rest.split_once("{\n"))
+        .map(|(_, body)| body)
+        .unwrap_or(body);
+
+    inner.strip_suffix("}\n").unwrap_or(inner)
+}
+
+/// Invoke the system `dot` tool to render `dot_source` to `path` in `format`,
+/// falling back to writing the `.dot` source with a warning if `dot` is not
+/// on the `PATH`.
+fn run_dot(dot_source: &str, path: &str, format: GraphvizFormat) {
+    let mut child = match Command::new("dot")
+        .arg(format!("-T{}", format.dot_type_flag()))
+        .arg("-o")
+        .arg(path)
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!(
+                "warning: could not run ‘dot’ to render {path} ({e}), writing dot source instead"
+            );
+
+            let fallback = Path::new(path).with_extension("dot");
+            let mut file = File::create(&fallback).expect("create file");
+            file.write_all(dot_source.as_bytes()).expect("write file");
+            return;
+        }
+    };
+
+    child
+        .stdin
+        .take()
+        .expect("piped stdin")
+        .write_all(dot_source.as_bytes())
+        .expect("write to dot");
+
+    let status = child.wait().expect("wait for dot");
+
+    if !status.success() {
+        eprintln!("warning: ‘dot’ exited with {status} while rendering {path}");
+    }
+}
+
+fn dot_source(verts: &[Vertex], name: &str, states: &[(usize, Vartable)]) -> String {
+    format!(
+        "strict digraph {name} {{\n{}}}\n",
+        cluster_source(verts, name, states)
+    )
+}
+
+/// Build the body (vertices and edges) of a single graph, suitable either as
+/// a standalone digraph or as the contents of a cluster subgraph.
+fn cluster_source(verts: &[Vertex], name: &str, states: &[(usize, Vartable)]) -> String {
+    let mut out = String::new();
 
     let mut vert_names = Vec::new();
 
     for (no, v) in verts.iter().enumerate() {
-        let name = if v.actions.iter().any(|a| matches!(a, Action::Done(..))) {
-            format!("done ({no})")
+        let vert_name = if v.actions.iter().any(|a| matches!(a, Action::Done(..))) {
+            format!("{name} done ({no})")
         } else {
-            format!("{} ({})", no_to_name(vert_names.len()), no)
+            format!("{name} {} ({})", no_to_name(vert_names.len()), no)
         };
 
         let mut labels: Vec<String> = v
@@ -62,20 +278,18 @@ pub(crate) fn graphviz(verts: &[Vertex], name: &str, states: &[(usize, Vartable)
         };
 
         if !labels.is_empty() {
-            writeln!(
-                &mut file,
-                "\t\"{}\" [label=\"{}\\n{}\"]{}",
-                name,
-                name,
+            out.push_str(&format!(
+                "\"{}\" [label=\"{}\\n{}\"]{}\n",
+                vert_name,
+                vert_name,
                 labels.join("\\n"),
                 color
-            )
-            .unwrap();
+            ));
         } else if !color.is_empty() {
-            writeln!(&mut file, "\t\"{name}\"{color}").unwrap();
+            out.push_str(&format!("\"{vert_name}\"{color}\n"));
         }
 
-        vert_names.push(name);
+        vert_names.push(vert_name);
     }
 
     for (i, v) in verts.iter().enumerate() {
@@ -85,63 +299,59 @@ pub(crate) fn graphviz(verts: &[Vertex], name: &str, states: &[(usize, Vartable)
                     length,
                     complete,
                     dest,
-                } => writeln!(
-                    &mut file,
-                    "\t\"{}\" -> \"{}\" [label=\"flash {} {}\"]",
+                } => out.push_str(&format!(
+                    "\"{}\" -> \"{}\" [label=\"flash {} {}\"]\n",
                     vert_names[i],
                     vert_names[*dest],
                     length,
                     if *complete { " complete" } else { "" }
-                )
-                .unwrap(),
+                )),
                 Edge::Gap {
                     length,
                     complete,
                     dest,
-                } => writeln!(
-                    &mut file,
-                    "\t\"{}\" -> \"{}\" [label=\"gap {} {}\"]",
+                } => out.push_str(&format!(
+                    "\"{}\" -> \"{}\" [label=\"gap {} {}\"]\n",
                     vert_names[i],
                     vert_names[*dest],
                     length,
                     if *complete { " complete" } else { "" }
-                )
-                .unwrap(),
+                )),
                 Edge::BranchCond { yes, no, .. } => {
-                    writeln!(
-                        &mut file,
-                        "\t\"{}\" -> \"{}\" [label=\"cond: true\"]",
+                    out.push_str(&format!(
+                        "\"{}\" -> \"{}\" [label=\"cond: true\"]\n",
                         vert_names[i], vert_names[*yes]
-                    )
-                    .unwrap();
-                    //
+                    ));
 
-                    writeln!(
-                        &mut file,
-                        "\t\"{}\" -> \"{}\" [label=\"cond: false\"]",
+                    out.push_str(&format!(
+                        "\"{}\" -> \"{}\" [label=\"cond: false\"]\n",
                         vert_names[i], vert_names[*no]
-                    )
-                    .unwrap();
+                    ));
                 }
                 Edge::MayBranchCond { dest, .. } => {
-                    writeln!(
-                        &mut file,
-                        "\t\"{}\" -> \"{}\" [label=\"may branch\"]",
+                    out.push_str(&format!(
+                        "\"{}\" -> \"{}\" [label=\"may branch\"]\n",
                         vert_names[i], vert_names[*dest]
-                    )
-                    .unwrap();
+                    ));
                 }
-                Edge::Branch(dest) => writeln!(
-                    &mut file,
-                    "\t\"{}\" -> \"{}\"",
+                Edge::Branch(dest) => out.push_str(&format!(
+                    "\"{}\" -> \"{}\"\n",
                     vert_names[i], vert_names[*dest]
-                )
-                .unwrap(),
+                )),
             }
         }
     }
 
-    writeln!(&mut file, "}}").unwrap();
+    out
+}
+
+/// Guess a `GraphvizFormat` from a path's extension, defaulting to `Dot`.
+pub(crate) fn format_from_extension(path: &Path) -> GraphvizFormat {
+    match path.extension().and_then(OsStr::to_str) {
+        Some("svg") => GraphvizFormat::Svg,
+        Some("png") => GraphvizFormat::Png,
+        _ => GraphvizFormat::Dot,
+    }
 }
 
 fn no_to_name(no: usize) -> String {
@@ -0,0 +1,96 @@
+use super::{
+    build_nfa::{Action, Edge, Vertex},
+    Expression, NFA,
+};
+
+/// The name of the synthetic variable set on entry to each constituent NFA,
+/// so a caller can tell which one of the merged NFAs produced a `Done`.
+pub const REMOTE_INDEX_VAR: &str = "$remote";
+
+/// Merge several NFAs into a single automaton for live decoding.
+///
+/// Feeding one `InfraredData` sample into a `Matcher` built from the
+/// result walks every constituent NFA in one pass, rather than the caller
+/// looping over one `Matcher` per NFA. Each original start vertex gains a
+/// `$remote = <index>` action, so the index of the NFA a `Done` came from
+/// can be read back out of the returned [`Vartable`](super::Vartable) via
+/// [`REMOTE_INDEX_VAR`].
+///
+/// Plain concatenation would leave vertex 0 of the first NFA as the only
+/// vertex reachable from outside the union, so every other constituent's
+/// start vertex is unreachable and never matches. Vertex 0 of the merged
+/// NFA is therefore a synthetic start that branches into every
+/// constituent's (now offset) start vertex instead.
+pub fn union(nfas: &[NFA]) -> NFA {
+    let mut verts = vec![Vertex {
+        actions: Vec::new(),
+        edges: Vec::new(),
+    }];
+
+    let mut start_edges = Vec::new();
+
+    for (index, nfa) in nfas.iter().enumerate() {
+        let offset = verts.len();
+
+        start_edges.push(Edge::Branch(offset));
+
+        for (no, vert) in nfa.verts.iter().enumerate() {
+            let mut actions = vert.actions.clone();
+
+            if no == 0 {
+                actions.insert(
+                    0,
+                    Action::Set {
+                        var: REMOTE_INDEX_VAR.to_string(),
+                        expr: Expression::Number(index as i64),
+                    },
+                );
+            }
+
+            let edges = vert
+                .edges
+                .iter()
+                .map(|edge| offset_edge(edge, offset))
+                .collect();
+
+            verts.push(Vertex { actions, edges });
+        }
+    }
+
+    verts[0].edges = start_edges;
+
+    NFA { verts }
+}
+
+fn offset_edge(edge: &Edge, offset: usize) -> Edge {
+    match edge {
+        Edge::Flash {
+            length,
+            complete,
+            dest,
+        } => Edge::Flash {
+            length: *length,
+            complete: *complete,
+            dest: dest + offset,
+        },
+        Edge::Gap {
+            length,
+            complete,
+            dest,
+        } => Edge::Gap {
+            length: *length,
+            complete: *complete,
+            dest: dest + offset,
+        },
+        Edge::BranchCond { expr, yes, no } => Edge::BranchCond {
+            expr: expr.clone(),
+            yes: yes + offset,
+            no: no + offset,
+        },
+        Edge::MayBranchCond { expr, dest } => Edge::MayBranchCond {
+            expr: expr.clone(),
+            dest: dest + offset,
+        },
+        Edge::Branch(dest) => Edge::Branch(dest + offset),
+    }
+}
@@ -1,20 +1,199 @@
 use super::{find_devices, Purpose};
 use cir::{
     lirc,
-    lircd_conf::{parse, Remote},
+    lircd_conf::{parse, RawCode, Remote},
     log::Log,
 };
-use irp::{mode2, rawir, InfraredData, Irp, Matcher, NFA};
+use irp::{graphviz::GraphvizFormat, mode2, nfa_union, rawir, InfraredData, Irp, NFA};
 use itertools::Itertools;
 use num_integer::Integer;
+use serde::Serialize;
 use std::{
+    collections::BTreeMap,
     fs,
     path::{Path, PathBuf},
 };
 
+/// How decoded events should be printed
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// `remote:NAME code:NAME` style human-readable lines (the default)
+    Text,
+    /// One JSON object per decoded event, newline-delimited
+    Json,
+}
+
+impl OutputFormat {
+    fn parse(matches: &clap::ArgMatches) -> OutputFormat {
+        match matches.value_of("OUTPUT") {
+            None | Some("text") => OutputFormat::Text,
+            Some("json") | Some("ndjson") => OutputFormat::Json,
+            Some(format) => {
+                eprintln!("error: ‘{}’ is not a valid output format", format);
+                std::process::exit(2);
+            }
+        }
+    }
+}
+
+/// Read `--tolerance`/`--eps`/`--aeps` into the two knobs `NFA::matcher()`
+/// takes, falling back to `default_tolerance` (the resolution of the
+/// receiver, when known) and the usual default absolute tolerance.
+fn matcher_tolerance(matches: &clap::ArgMatches, default_tolerance: u32) -> (u32, u32) {
+    let tolerance = matches
+        .value_of("TOLERANCE")
+        .or_else(|| matches.value_of("EPS"))
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default_tolerance);
+
+    let aeps = matches
+        .value_of("AEPS")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(100);
+
+    (tolerance, aeps)
+}
+
+/// A running histogram of observed flash/gap durations, used in learning
+/// mode to report the dominant pulse/space lengths and estimated carrier,
+/// and to derive a tolerance that tracks drifting timings.
+#[derive(Default)]
+struct LearningStats {
+    flash: BTreeMap<u32, u32>,
+    gap: BTreeMap<u32, u32>,
+    carrier: Option<u32>,
+}
+
+impl LearningStats {
+    const BUCKET: u32 = 50;
+
+    fn observe_flash(&mut self, length: u32) {
+        *self
+            .flash
+            .entry(length / Self::BUCKET * Self::BUCKET)
+            .or_insert(0) += 1;
+    }
+
+    fn observe_gap(&mut self, length: u32) {
+        *self
+            .gap
+            .entry(length / Self::BUCKET * Self::BUCKET)
+            .or_insert(0) += 1;
+    }
+
+    fn observe_carrier(&mut self, hz: u32) {
+        self.carrier = Some(hz);
+    }
+
+    fn dominant(histogram: &BTreeMap<u32, u32>) -> Option<u32> {
+        histogram
+            .iter()
+            .max_by_key(|(_, count)| **count)
+            .map(|(length, _)| *length)
+    }
+
+    /// Derive an absolute tolerance (in microseconds) from how far observed
+    /// durations spread around their dominant bucket, so marginal remotes
+    /// with drifting timings keep decoding reliably.
+    fn derived_aeps(&self) -> Option<u32> {
+        let spread = |histogram: &BTreeMap<u32, u32>| -> Option<u32> {
+            let dominant = Self::dominant(histogram)?;
+
+            histogram
+                .keys()
+                .map(|length| length.abs_diff(dominant))
+                .max()
+        };
+
+        match (spread(&self.flash), spread(&self.gap)) {
+            (None, None) => None,
+            (a, b) => Some(a.unwrap_or(0).max(b.unwrap_or(0)).max(100)),
+        }
+    }
+
+    fn report(&self, log: &Log) {
+        if let Some(hz) = self.carrier {
+            log.info(&format!("learning: estimated carrier {} Hz", hz));
+        }
+
+        if let Some(flash) = Self::dominant(&self.flash) {
+            log.info(&format!("learning: dominant pulse length {} us", flash));
+        }
+
+        if let Some(gap) = Self::dominant(&self.gap) {
+            log.info(&format!("learning: dominant gap length {} us", gap));
+        }
+    }
+}
+
+fn parse_graphviz_format(matches: &clap::ArgMatches) -> GraphvizFormat {
+    match matches.value_of("GRAPHVIZ_FORMAT") {
+        None | Some("dot") => GraphvizFormat::Dot,
+        Some("svg") => GraphvizFormat::Svg,
+        Some("png") => GraphvizFormat::Png,
+        Some(format) => {
+            eprintln!("error: ‘{}’ is not a valid graphviz format", format);
+            std::process::exit(2);
+        }
+    }
+}
+
+/// Where the raw IR samples that produced a decoded event came from
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Origin<'a> {
+    File { path: &'a str },
+    Rawir { index: usize },
+    Lirc { device: &'a str },
+}
+
+/// A single decoded event, emitted as one JSON object in `--output json` mode
+#[derive(Serialize)]
+struct DecodeEvent<'a> {
+    source: &'a str,
+    origin: Origin<'a>,
+    remote: Option<&'a str>,
+    code: Option<&'a str>,
+    scancode: Option<u64>,
+    vars: BTreeMap<&'a str, i64>,
+}
+
+impl<'a> DecodeEvent<'a> {
+    fn emit(&self, format: OutputFormat) {
+        match format {
+            OutputFormat::Text => {
+                if let Some(remote) = self.remote {
+                    if let Some(code) = self.code {
+                        println!("remote:{} code:{}", remote, code);
+                    } else {
+                        println!(
+                            "remote:{} unmapped code:{:x}",
+                            remote,
+                            self.scancode.unwrap_or_default()
+                        );
+                    }
+                } else {
+                    println!(
+                        "decoded: {}",
+                        self.vars
+                            .iter()
+                            .map(|(name, val)| format!("{}={:x}", name, val))
+                            .join(", ")
+                    );
+                }
+            }
+            OutputFormat::Json => {
+                println!("{}", serde_json::to_string(self).expect("serialize"));
+            }
+        }
+    }
+}
+
 pub fn decode(matches: &clap::ArgMatches, log: &Log) {
     let remotes;
     let graphviz = matches.is_present("GRAPHVIZ");
+    let graphviz_format = parse_graphviz_format(matches);
+    let output = OutputFormat::parse(matches);
 
     let irps = if let Some(i) = matches.value_of("IRP") {
         let irp = match Irp::parse(i) {
@@ -28,19 +207,21 @@ pub fn decode(matches: &clap::ArgMatches, log: &Log) {
         let nfa = irp.build_nfa().unwrap();
 
         if graphviz {
-            let filename = "irp_nfa.dot";
+            let filename = format!("irp_nfa.{}", graphviz_format.extension());
             log.info(&format!("saving nfa as {}", filename));
 
-            nfa.dotgraphviz(filename);
+            nfa.dotgraphviz(&filename);
         }
 
-        vec![(None, nfa)]
+        vec![(None, nfa, i.to_string())]
     } else if let Some(filename) = matches.value_of_os("LIRCDCONF") {
         remotes = match parse(filename, log) {
             Ok(r) => r,
             Err(_) => std::process::exit(2),
         };
 
+        let source = Path::new(filename).display().to_string();
+
         remotes
             .iter()
             .map(|remote| {
@@ -54,13 +235,13 @@ pub fn decode(matches: &clap::ArgMatches, log: &Log) {
                 let nfa = irp.build_nfa().unwrap();
 
                 if graphviz {
-                    let filename = format!("{}_nfa.dot", remote.name);
+                    let filename = format!("{}_nfa.{}", remote.name, graphviz_format.extension());
                     log.info(&format!("saving nfa as {}", filename));
 
                     nfa.dotgraphviz(&filename);
                 }
 
-                (Some(remote), nfa)
+                (Some(remote), nfa, source.clone())
             })
             .collect()
     } else {
@@ -86,10 +267,14 @@ pub fn decode(matches: &clap::ArgMatches, log: &Log) {
                 filename.to_string_lossy()
             ));
 
+            let origin = Origin::File {
+                path: &filename.to_string_lossy(),
+            };
+
             match rawir::parse(&input) {
                 Ok(raw) => {
                     log.info(&format!("decoding: {}", rawir::print_to_string(&raw)));
-                    process(&raw, &irps, matches, log);
+                    process(&raw, &irps, &origin, output, matches, log);
                 }
                 Err(msg) => {
                     log.info(&format!(
@@ -100,7 +285,7 @@ pub fn decode(matches: &clap::ArgMatches, log: &Log) {
                     match mode2::parse(&input) {
                         Ok(m) => {
                             log.info(&format!("decoding: {}", rawir::print_to_string(&m.raw)));
-                            process(&m.raw, &irps, matches, log);
+                            process(&m.raw, &irps, &origin, output, matches, log);
                         }
                         Err((line_no, error)) => {
                             log.error(&format!(
@@ -125,11 +310,11 @@ pub fn decode(matches: &clap::ArgMatches, log: &Log) {
     if let Some(rawirs) = matches.values_of("RAWIR") {
         input_on_cli = true;
 
-        for rawir in rawirs {
+        for (index, rawir) in rawirs.enumerate() {
             match rawir::parse(rawir) {
                 Ok(raw) => {
                     log.info(&format!("decoding: {}", rawir::print_to_string(&raw)));
-                    process(&raw, &irps, matches, log);
+                    process(&raw, &irps, &Origin::Rawir { index }, output, matches, log);
                 }
                 Err(msg) => {
                     log.error(&format!("parsing ‘{}’: {}", rawir, msg));
@@ -154,9 +339,9 @@ pub fn decode(matches: &clap::ArgMatches, log: &Log) {
                 }
             };
 
-            if matches.is_present("LEARNING") {
-                let mut learning_mode = false;
+            let mut learning_mode = false;
 
+            if matches.is_present("LEARNING") {
                 if lircdev.can_measure_carrier() {
                     if let Err(err) = lircdev.set_measure_carrier(true) {
                         eprintln!(
@@ -191,11 +376,36 @@ pub fn decode(matches: &clap::ArgMatches, log: &Log) {
             if lircdev.can_receive_raw() {
                 let mut rawbuf = Vec::with_capacity(1024);
                 let resolution = lircdev.receiver_resolution().unwrap_or(100);
-
-                let mut matchers = irps
+                let origin = Origin::Lirc {
+                    device: &lircdev.to_string(),
+                };
+
+                // One matcher per loaded remote/IRP, not a single matcher over
+                // their nfa_union::union - a merged matcher's input() returns
+                // at most one Vartable per sample, so when two loaded remotes
+                // both accept the same IR only one event would ever be
+                // emitted. Keeping them separate, as process() already does
+                // for file/rawir input, means every remote that accepts a
+                // sample gets its own decode event, matching the pre-merge
+                // behaviour.
+                let (tolerance, mut aeps) = matcher_tolerance(matches, resolution);
+                let mut matchers: Vec<_> = irps
                     .iter()
-                    .map(|(remote, nfa)| (remote, nfa.matcher(resolution, 100)))
-                    .collect::<Vec<(&Option<&Remote>, Matcher)>>();
+                    .map(|(_, nfa, _)| nfa.matcher(tolerance, aeps))
+                    .collect();
+                let mut learning_stats = LearningStats::default();
+                let mut samples_since_report = 0u32;
+
+                // A raw_codes button press spans many samples, so the
+                // sequence has to be accumulated across receive_raw() calls
+                // and matched when a timeout ends the frame (a buffer
+                // overflow makes the accumulated timings untrustworthy, so it
+                // discards the frame without matching it),
+                // rather than re-scanned from whatever happens to be in a
+                // single buffer chunk - which rarely lines up with a whole
+                // press and would let the same press match on every
+                // iteration it remains in the buffer.
+                let mut frame: Vec<u32> = Vec::new();
 
                 loop {
                     if let Err(err) = lircdev.receive_raw(&mut rawbuf) {
@@ -207,42 +417,67 @@ pub fn decode(matches: &clap::ArgMatches, log: &Log) {
 
                     for raw in &rawbuf {
                         let ir = if raw.is_pulse() {
+                            if learning_mode {
+                                learning_stats.observe_flash(raw.value());
+                            }
+                            frame.push(raw.value());
                             InfraredData::Flash(raw.value())
-                        } else if raw.is_space() || raw.is_timeout() {
+                        } else if raw.is_space() {
+                            if learning_mode {
+                                learning_stats.observe_gap(raw.value());
+                            }
+                            frame.push(raw.value());
+                            InfraredData::Gap(raw.value())
+                        } else if raw.is_timeout() {
+                            if learning_mode {
+                                learning_stats.observe_gap(raw.value());
+                            }
+
+                            for (remote, _, source) in &irps {
+                                if let Some(remote) = remote {
+                                    if let Some(code) = match_raw_code(remote, &frame) {
+                                        emit_raw_decode(remote, source, code, &origin, output);
+                                    }
+                                }
+                            }
+                            frame.clear();
+
                             InfraredData::Gap(raw.value())
                         } else if raw.is_overflow() {
+                            frame.clear();
                             InfraredData::Reset
+                        } else if learning_mode && raw.is_frequency() {
+                            learning_stats.observe_carrier(raw.value());
+                            continue;
                         } else {
                             continue;
                         };
 
-                        for (remote, matcher) in &mut matchers {
+                        for (matcher, (remote, _, source)) in matchers.iter_mut().zip(irps.iter()) {
                             if let Some(var) = matcher.input(ir) {
-                                if let Some(remote) = remote {
-                                    // lirc
-                                    let decoded_code = var["CODE"] as u64;
-
-                                    // TODO: raw codes
-                                    if let Some(code) = remote
-                                        .codes
-                                        .iter()
-                                        .find(|code| code.code[0] == decoded_code)
-                                    {
-                                        println!("remote:{} code:{}", remote.name, code.name);
-                                    } else {
-                                        println!(
-                                            "remote:{} unmapped code:{:x}",
-                                            remote.name, decoded_code
-                                        );
+                                emit_decode(remote, source, &var, &origin, output);
+                            }
+                        }
+
+                        if learning_mode {
+                            samples_since_report += 1;
+
+                            if samples_since_report >= 32 {
+                                samples_since_report = 0;
+                                learning_stats.report(log);
+
+                                if let Some(derived) = learning_stats.derived_aeps() {
+                                    if derived != aeps {
+                                        log.info(&format!(
+                                            "learning: adjusting tolerance to {} us",
+                                            derived
+                                        ));
+                                        aeps = derived;
+                                        matchers = irps
+                                            .iter()
+                                            .map(|(_, nfa, _)| nfa.matcher(tolerance, aeps))
+                                            .collect();
                                     }
-                                } else {
-                                    // lirc remote
-                                    println!(
-                                        "decoded: {}",
-                                        var.iter()
-                                            .map(|(name, val)| format!("{}={:x}", name, val))
-                                            .join(", ")
-                                    );
                                 }
                             }
                         }
@@ -256,11 +491,80 @@ pub fn decode(matches: &clap::ArgMatches, log: &Log) {
     }
 }
 
-fn process(raw: &[u32], irps: &[(Option<&Remote>, NFA)], matches: &clap::ArgMatches, log: &Log) {
-    let graphviz = matches.is_present("GRAPHVIZ");
+fn emit_decode(
+    remote: &Option<&Remote>,
+    source: &str,
+    var: &irp::Vartable,
+    origin: &Origin,
+    output: OutputFormat,
+) {
+    let vars: BTreeMap<&str, i64> = var
+        .iter()
+        .filter(|(name, _)| *name != nfa_union::REMOTE_INDEX_VAR)
+        .map(|(name, val)| (name, val))
+        .collect();
+
+    if let Some(remote) = remote {
+        // lirc
+        let decoded_code = var["CODE"] as u64;
+
+        // raw_codes buttons are not looked up here; match_raw_code() handles them
+        let code = remote
+            .codes
+            .iter()
+            .find(|code| code.code[0] == decoded_code);
+
+        DecodeEvent {
+            source,
+            origin: match origin {
+                Origin::File { path } => Origin::File { path },
+                Origin::Rawir { index } => Origin::Rawir { index: *index },
+                Origin::Lirc { device } => Origin::Lirc { device },
+            },
+            remote: Some(&remote.name),
+            code: code.map(|code| code.name.as_str()),
+            scancode: Some(decoded_code),
+            vars,
+        }
+        .emit(output);
+    } else {
+        // irp
+        DecodeEvent {
+            source,
+            origin: match origin {
+                Origin::File { path } => Origin::File { path },
+                Origin::Rawir { index } => Origin::Rawir { index: *index },
+                Origin::Lirc { device } => Origin::Lirc { device },
+            },
+            remote: None,
+            code: None,
+            scancode: None,
+            vars,
+        }
+        .emit(output);
+    }
+}
 
-    for (remote, nfa) in irps {
-        let mut matcher = nfa.matcher(100, 100);
+fn process(
+    raw: &[u32],
+    irps: &[(Option<&Remote>, NFA, String)],
+    origin: &Origin,
+    output: OutputFormat,
+    matches: &clap::ArgMatches,
+    log: &Log,
+) {
+    let graphviz = matches.is_present("GRAPHVIZ");
+    let graphviz_format = parse_graphviz_format(matches);
+    let (tolerance, aeps) = matcher_tolerance(matches, 100);
+
+    for (remote, nfa, source) in irps {
+        let mut matcher = nfa.matcher(tolerance, aeps);
+        let name = if let Some(remote) = remote {
+            &remote.name
+        } else {
+            "irp"
+        };
+        let mut step_files = Vec::new();
 
         for (index, raw) in raw.iter().enumerate() {
             let ir = if index.is_odd() {
@@ -270,46 +574,87 @@ fn process(raw: &[u32], irps: &[(Option<&Remote>, NFA)], matches: &clap::ArgMatc
             };
 
             if let Some(var) = matcher.input(ir) {
-                if let Some(remote) = remote {
-                    // lirc
-                    let decoded_code = var["CODE"] as u64;
-
-                    // TODO: raw codes
-                    if let Some(code) = remote
-                        .codes
-                        .iter()
-                        .find(|code| code.code[0] == decoded_code)
-                    {
-                        println!("remote:{} code:{}", remote.name, code.name);
-                    } else {
-                        println!("remote:{} unmapped code:{:x}", remote.name, decoded_code);
-                    }
-                } else {
-                    // lirc remote
-                    println!(
-                        "decoded: {}",
-                        var.iter()
-                            .map(|(name, val)| format!("{}={:x}", name, val))
-                            .join(", ")
-                    );
-                }
+                emit_decode(remote, source, &var, origin, output);
             }
 
             if graphviz {
-                let filename = format!(
-                    "{}_nfa_step_{:04}.dot",
-                    if let Some(remote) = remote {
-                        &remote.name
-                    } else {
-                        "irp"
-                    },
-                    index
-                );
+                // each step is dumped to its own dot file first; they get
+                // folded into one combined artifact once the whole buffer
+                // has been decoded, see below.
+                let filename = format!("{name}_nfa_step_{index:04}.dot");
 
                 log.info(&format!("saving nfa at step {} as {}", index, filename));
 
                 matcher.dotgraphviz(&filename);
+                step_files.push(PathBuf::from(filename));
+            }
+        }
+
+        if graphviz && !step_files.is_empty() {
+            let filename = format!("{name}_nfa_steps.{}", graphviz_format.extension());
+
+            log.info(&format!(
+                "combining {} steps into {}",
+                step_files.len(),
+                filename
+            ));
+
+            irp::graphviz::combine_steps(&step_files, name, &filename, graphviz_format);
+        }
+
+        if let Some(remote) = remote {
+            if let Some(code) = match_raw_code(remote, raw) {
+                emit_raw_decode(remote, source, code, origin, output);
+            }
+        }
+    }
+}
+
+/// Find the `raw_codes` button whose timing sequence matches `raw`, within
+/// the remote's own tolerance settings.
+fn match_raw_code<'a>(remote: &'a Remote, raw: &[u32]) -> Option<&'a RawCode> {
+    remote
+        .raw_codes
+        .iter()
+        .find(|code| raw_sequence_matches(&code.rawir, raw, remote.eps, remote.aeps))
+}
+
+/// Compare two pulse/space sequences for equality within `eps` percent or
+/// `aeps` microseconds, whichever is larger, per entry.
+fn raw_sequence_matches(expected: &[u32], observed: &[u32], eps: u32, aeps: u32) -> bool {
+    expected.len() == observed.len()
+        && expected.iter().zip(observed).all(|(expected, observed)| {
+            let tolerance = std::cmp::max(aeps, expected * eps / 100);
+
+            expected.abs_diff(*observed) <= tolerance
+        })
+}
+
+fn emit_raw_decode(
+    remote: &Remote,
+    source: &str,
+    code: &RawCode,
+    origin: &Origin,
+    output: OutputFormat,
+) {
+    match output {
+        OutputFormat::Text => {
+            println!("remote:{} code:{}", remote.name, code.name);
+        }
+        OutputFormat::Json => {
+            DecodeEvent {
+                source,
+                origin: match origin {
+                    Origin::File { path } => Origin::File { path },
+                    Origin::Rawir { index } => Origin::Rawir { index: *index },
+                    Origin::Lirc { device } => Origin::Lirc { device },
+                },
+                remote: Some(&remote.name),
+                code: Some(&code.name),
+                scancode: None,
+                vars: BTreeMap::new(),
             }
+            .emit(output);
         }
     }
 }